@@ -0,0 +1,125 @@
+//! Async connection backend for `redis-governor`.
+//!
+//! `governor::RateLimiter` is generic over a synchronous
+//! [`StateStore`](governor::state::StateStore), so it can't be driven
+//! from async code directly. Instead [`AsyncGovernorInstance::check_key`]
+//! makes the same GCRA decision (see [`crate::gcra`]) itself, server-side
+//! in a single `EVALSHA` via [`script::AsyncGcraScriptLimiter`], so the
+//! whole round trip can be `.await`ed without parking an OS thread - and
+//! without the WATCH/MULTI loop the sync backend uses, which isn't safe
+//! over the multiplexed connection this backend shares across instances
+//! (see [`script`] for why).
+
+pub mod clock;
+pub mod script;
+
+use governor::Quota;
+use std::borrow::Cow;
+use std::fmt::Debug;
+use std::hash::Hash;
+use std::marker::PhantomData;
+use std::sync::Arc;
+use std::time::Duration;
+
+use tokio::sync::Mutex;
+
+use crate::error::RedisGovernorError;
+
+/// Returned by [`AsyncGovernorInstance::check_key`] when a cell is
+/// rejected.
+///
+/// Unlike [`governor::NotUntil`], this is built directly from the GCRA
+/// decision made against Redis rather than from a running
+/// [`governor::RateLimiter`], since the async path doesn't use one.
+#[derive(Copy, Clone, Debug)]
+pub struct AsyncNotUntil {
+    retry_after_nanos: u64,
+}
+
+impl AsyncNotUntil {
+    /// How long the caller should wait before retrying.
+    pub fn wait_time(&self) -> Duration {
+        Duration::from_nanos(self.retry_after_nanos)
+    }
+}
+
+/// An async instance of a Governor with a reserved connection to Redis.
+pub struct AsyncGovernorInstance<C, K> {
+    limiter: script::AsyncGcraScriptLimiter<C, K>,
+    clock: clock::AsyncRedisClock<C>,
+}
+
+impl<C, K> AsyncGovernorInstance<C, K>
+where
+    K: Hash + Eq + Clone + Debug + Sync,
+    C: redis::aio::ConnectionLike + Send,
+{
+    /// Get a reference to the stored [`AsyncRedisClock`](clock::AsyncRedisClock).
+    pub fn clock(&self) -> &clock::AsyncRedisClock<C> {
+        &self.clock
+    }
+
+    /// Check a single key against `quota`, making the GCRA admit/deny
+    /// decision against Redis in a single atomic `EVALSHA` (after one
+    /// `TIME` read), both `.await`ed instead of blocking a thread.
+    pub async fn check_key(
+        &self,
+        key: &K,
+        quota: Quota,
+    ) -> Result<Result<(), AsyncNotUntil>, RedisGovernorError> {
+        self.limiter.check_key(key, &quota).await
+    }
+}
+
+/// A governor rate limiter using Redis as a distributed store, reachable
+/// from async contexts.
+///
+/// Mirrors [`RedisGovernor`](crate::RedisGovernor), but hands out
+/// [`AsyncGovernorInstance`]s backed by a shared
+/// [`redis::aio::ConnectionManager`] (which already multiplexes
+/// reconnects) rather than an r2d2-pooled blocking connection per
+/// instance. Every instance this factory hands out shares the *same*
+/// underlying connection, which is why [`AsyncGovernorInstance::check_key`]
+/// has to make its GCRA decision in one atomic command rather than a
+/// WATCH/MULTI loop - see [`script`].
+#[derive(Clone)]
+pub struct AsyncRedisGovernor<K> {
+    conn: redis::aio::ConnectionManager,
+    prefix: Cow<'static, str>,
+    key: PhantomData<K>,
+}
+
+impl<K> AsyncRedisGovernor<K>
+where
+    K: Hash + Eq + Clone + Debug,
+{
+    /// Create a new [`AsyncRedisGovernor`](Self) for an existing Redis
+    /// client.
+    ///
+    /// See [`RedisGovernor::new`](crate::RedisGovernor::new) for the
+    /// meaning of `prefix`.
+    ///
+    /// Fails if the initial connection to Redis can't be established.
+    pub async fn new<I>(client: redis::Client, prefix: I) -> Result<Self, RedisGovernorError>
+    where
+        I: Into<Cow<'static, str>>,
+    {
+        let conn = client.get_tokio_connection_manager().await?;
+
+        Ok(Self {
+            conn,
+            prefix: prefix.into(),
+            key: Default::default(),
+        })
+    }
+
+    pub fn instance(&self) -> AsyncGovernorInstance<redis::aio::ConnectionManager, K> {
+        let conn = Arc::new(Mutex::new(self.conn.clone()));
+        let clock = clock::AsyncRedisClock::new(conn.clone(), &self.prefix);
+
+        AsyncGovernorInstance {
+            limiter: script::AsyncGcraScriptLimiter::new(conn, clock.clone(), self.prefix.clone()),
+            clock,
+        }
+    }
+}
@@ -0,0 +1,82 @@
+use crate::clock::RedisInstant;
+use crate::error::RedisGovernorError;
+use governor::nanos::Nanos;
+use std::sync::Arc;
+
+use tokio::sync::Mutex;
+
+/// Async counterpart of [`RedisClock`](crate::clock::RedisClock).
+///
+/// Uses `Arc<Mutex<redis::aio::...>>` rather than `Rc<RefCell<_>>`, since
+/// `RedisClock`'s connection is pinned to one thread and can't cross an
+/// `.await` point.
+///
+/// Unlike `RedisClock`, this isn't constrained by `governor::Clock`'s
+/// synchronous, infallible `now`/`start` signatures, so both methods
+/// here just return `Result` directly - there's no trait-fixed return
+/// type forcing a panicking wrapper around a fallible `try_*` twin.
+pub struct AsyncRedisClock<C> {
+    pub(crate) conn: Arc<Mutex<C>>,
+    start_key: String,
+}
+
+impl<C> Clone for AsyncRedisClock<C> {
+    fn clone(&self) -> Self {
+        Self {
+            conn: self.conn.clone(),
+            start_key: self.start_key.clone(),
+        }
+    }
+}
+
+impl<C> AsyncRedisClock<C> {
+    pub(crate) fn new(conn: Arc<Mutex<C>>, prefix: &str) -> Self {
+        Self {
+            conn,
+            start_key: format!("{}:start", prefix),
+        }
+    }
+}
+
+impl<C> AsyncRedisClock<C>
+where
+    C: redis::aio::ConnectionLike,
+{
+    async fn now_nanos(conn: &mut C) -> Result<u64, RedisGovernorError> {
+        let (secs, micros): (u64, u64) = redis::cmd("TIME").query_async(conn).await?;
+        Ok(secs * 1_000_000_000 + micros * 1_000)
+    }
+
+    /// Current time, as observed by Redis.
+    pub async fn now(&self) -> Result<RedisInstant, RedisGovernorError> {
+        let mut conn = self.conn.lock().await;
+        Ok(RedisInstant::from_nanos(Nanos::new(
+            Self::now_nanos(&mut conn).await?,
+        )))
+    }
+
+    /// The shared starting reference point for this governor's keys,
+    /// creating it if this is the first caller to ask.
+    ///
+    /// The sync `RedisClock::try_start` does this with a WATCH/MULTI
+    /// loop, which - like any check-and-set loop on this async backend,
+    /// see [`crate::aio::script`] - isn't
+    /// safe when `conn` may be multiplexed with other callers on the
+    /// same underlying connection. `SET ... NX GET` sets the key only if
+    /// it's absent and always reports what's stored, in one atomic
+    /// round trip, so there's no WATCH to race against at all.
+    pub async fn start(&self) -> Result<RedisInstant, RedisGovernorError> {
+        let mut conn = self.conn.lock().await;
+        let now = Self::now_nanos(&mut conn).await?;
+
+        let prev: Option<u64> = redis::cmd("SET")
+            .arg(&self.start_key)
+            .arg(now)
+            .arg("NX")
+            .arg("GET")
+            .query_async(&mut *conn)
+            .await?;
+
+        Ok(RedisInstant::from_nanos(Nanos::new(prev.unwrap_or(now))))
+    }
+}
@@ -0,0 +1,102 @@
+use std::borrow::Cow;
+use std::hash::{Hash, Hasher};
+use std::marker::PhantomData;
+use std::sync::Arc;
+
+use governor::Quota;
+use redis::Script;
+use siphasher::sip::SipHasher;
+
+use tokio::sync::Mutex;
+
+use crate::error::RedisGovernorError;
+use crate::gcra::GcraParameters;
+use crate::script::GCRA_SCRIPT;
+
+use super::clock::AsyncRedisClock;
+use super::AsyncNotUntil;
+
+/// Async counterpart of [`GcraScriptLimiter`](crate::script::GcraScriptLimiter).
+///
+/// [`AsyncRedisGovernor`](super::AsyncRedisGovernor) hands every
+/// [`AsyncGovernorInstance`](super::AsyncGovernorInstance) a clone of the
+/// same multiplexed `redis::aio::ConnectionManager`, so many instances'
+/// commands can interleave on the one underlying server connection. A
+/// WATCH/MULTI check-and-set loop - what this crate's other state stores
+/// use - isn't safe there: WATCH doesn't abort a transaction when the
+/// watched key is written by the *same* connection, so two interleaved
+/// instances could both read the old TAT and both "win". Making the
+/// whole read-decide-write GCRA decision inside one `EVALSHA`, the same
+/// way [`GcraScriptLimiter`](crate::script::GcraScriptLimiter) does for
+/// the sync backend, needs no WATCH at all: the single command is
+/// already atomic, so interleaving other commands around it is fine.
+pub struct AsyncGcraScriptLimiter<C, K> {
+    conn: Arc<Mutex<C>>,
+    clock: AsyncRedisClock<C>,
+    prefix: Cow<'static, str>,
+    script: Script,
+    key: PhantomData<K>,
+}
+
+impl<C, K> AsyncGcraScriptLimiter<C, K> {
+    pub(crate) fn new<I: Into<Cow<'static, str>>>(
+        conn: Arc<Mutex<C>>,
+        clock: AsyncRedisClock<C>,
+        prefix: I,
+    ) -> Self {
+        Self {
+            conn,
+            clock,
+            prefix: prefix.into(),
+            script: Script::new(GCRA_SCRIPT),
+            key: Default::default(),
+        }
+    }
+}
+
+impl<C, K> AsyncGcraScriptLimiter<C, K>
+where
+    C: redis::aio::ConnectionLike + Send,
+    K: Hash,
+{
+    fn tat_key(&self, key: &K) -> String {
+        let mut hasher = SipHasher::new();
+        key.hash(&mut hasher);
+        format!("{{{}}}:governor:value:{:x}", self.prefix, hasher.finish())
+    }
+
+    /// Check `key` against `quota`: one `TIME` read, then the decision
+    /// in a single atomic `EVALSHA`.
+    pub async fn check_key(
+        &self,
+        key: &K,
+        quota: &Quota,
+    ) -> Result<Result<(), AsyncNotUntil>, RedisGovernorError> {
+        let params = GcraParameters::from_quota(quota);
+        let now_nanos = self.clock.now().await?.nanos();
+        let mut conn = self.conn.lock().await;
+
+        let (allowed, retry_after_nanos): (i64, i64) = self
+            .script
+            .key(self.tat_key(key))
+            .arg(now_nanos)
+            .arg(params.emission_interval_nanos)
+            .arg(params.delay_variation_tolerance_nanos)
+            .arg(params.burst)
+            .arg(1u32)
+            .invoke_async(&mut *conn)
+            .await?;
+
+        Ok(Self::decode(allowed, retry_after_nanos))
+    }
+
+    fn decode(allowed: i64, retry_after_nanos: i64) -> Result<(), AsyncNotUntil> {
+        if allowed == 1 {
+            Ok(())
+        } else {
+            Err(AsyncNotUntil {
+                retry_after_nanos: retry_after_nanos as u64,
+            })
+        }
+    }
+}
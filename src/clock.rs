@@ -4,12 +4,15 @@ use std::cell::RefCell;
 use std::ops::Add;
 use std::rc::Rc;
 
+use crate::error::RedisGovernorError;
+
 /// Clock source for using Redis as a limiter time base.
 ///
 /// Uses `Rc<RefCell<redis::Connection>>` as `Clock` requires that `Clone` be implemented for the type.
 pub struct RedisClock<C> {
     pub(crate) conn: Rc<RefCell<C>>,
     start_key: String,
+    replica_conn: Option<Rc<RefCell<C>>>,
 }
 
 // This impl is used because derive places a Clone bound on C,
@@ -20,6 +23,7 @@ impl<C> Clone for RedisClock<C> {
         Self {
             conn: self.conn.clone(),
             start_key: self.start_key.clone(),
+            replica_conn: self.replica_conn.clone(),
         }
     }
 }
@@ -28,48 +32,68 @@ impl<C> RedisClock<C> {
     pub(crate) fn new(conn: Rc<RefCell<C>>, prefix: &str) -> Self {
         Self {
             conn,
-            start_key: format!("{}:start", prefix),
+            // Hash-tagged so this lands in the same Cluster slot as
+            // `RedisStateStore`'s hash key and value keys for the same
+            // prefix - see the comment there.
+            start_key: format!("{{{}}}:start", prefix),
+            replica_conn: None,
         }
     }
+
+    /// Route the pure, never-mutating `TIME` read made by [`Clock::now`]
+    /// over `replica_conn` instead of the primary connection; `start`'s
+    /// CAS and every `measure_and_replace` write always stay on the
+    /// primary regardless.
+    ///
+    /// `replica_conn` is expected to already be routed to a Cluster
+    /// replica by however it was obtained (e.g. pooled from a
+    /// `redis::cluster::ClusterClient` built with
+    /// `ClusterClientBuilder::read_from_replicas`) - replica routing is a
+    /// property of the client/connection, not something this crate can
+    /// toggle per call on an existing one.
+    pub fn with_replica_conn(mut self, replica_conn: Rc<RefCell<C>>) -> Self {
+        self.replica_conn = Some(replica_conn);
+        self
+    }
 }
 
 impl<C> RedisClock<C>
 where
     C: redis::ConnectionLike,
 {
-    fn now_nanos(conn: &mut C) -> u64 {
-        let (secs, micros): (u64, u64) = redis::cmd("TIME")
-            .query(conn)
-            .expect("Failed to retrieve time from Redis");
-
-        secs * 1_000_000_000 + micros * 1_000
+    fn try_now_nanos(conn: &mut C) -> Result<u64, RedisGovernorError> {
+        let (secs, micros): (u64, u64) = redis::cmd("TIME").query(conn)?;
+        Ok(secs * 1_000_000_000 + micros * 1_000)
     }
 
     pub(crate) fn reset_start(&self) {}
-}
-
-impl<C> Clock for RedisClock<C>
-where
-    C: redis::ConnectionLike,
-{
-    type Instant = RedisInstant;
 
-    fn now(&self) -> Self::Instant {
-        RedisInstant(Nanos::new(Self::now_nanos(&mut *self.conn.borrow_mut())))
+    /// Same as [`Clock::now`], but surfaces a failed `TIME` round trip
+    /// as `Err(RedisGovernorError)` instead of panicking.
+    pub fn try_now(&self) -> Result<RedisInstant, RedisGovernorError> {
+        let conn = self.replica_conn.as_ref().unwrap_or(&self.conn);
+        Self::try_now_nanos(&mut conn.borrow_mut()).map(|n| RedisInstant(Nanos::new(n)))
     }
 
-    fn start(&self) -> Self::Instant {
+    /// Same as [`Clock::start`], but surfaces a failed Redis round trip
+    /// as `Err(RedisGovernorError)` instead of panicking.
+    ///
+    /// `Clock::now`/`start` fix their return type to `Self::Instant`
+    /// with no room for a `Result` at all, so this (and
+    /// [`try_now`](Self::try_now)) is the only way to observe a Redis
+    /// failure here without crashing; the trait impl below calls these
+    /// and panics at that one, documented boundary.
+    pub fn try_start(&self) -> Result<RedisInstant, RedisGovernorError> {
         let conn = &mut *self.conn.borrow_mut();
-        redis_check_and_set!(conn, (&self.start_key) => {
-            let start: Option<u64> = redis::Cmd::get(&self.start_key)
-                .query(conn)
-                .expect("Failed to check Redis for key presence");
+        loop {
+            let _: () = redis::cmd("WATCH").arg(&self.start_key).query(conn)?;
 
+            let start: Option<u64> = redis::Cmd::get(&self.start_key).query(conn)?;
             if let Some(start) = start {
-                return RedisInstant(Nanos::new(start));
+                return Ok(RedisInstant(Nanos::new(start)));
             }
 
-            let now = Self::now_nanos(conn);
+            let now = Self::try_now_nanos(conn)?;
 
             let response: Option<(u64,)> = redis::pipe()
                 .atomic()
@@ -79,14 +103,32 @@ where
                 .ignore()
                 .cmd("GET")
                 .arg(&self.start_key)
-                .query(conn)
-                .expect("Failed to set start time");
+                .query(conn)?;
 
             match response {
                 None => continue,
-                Some((val,)) => return RedisInstant(Nanos::new(val)),
+                Some((val,)) => return Ok(RedisInstant(Nanos::new(val))),
             }
-        });
+        }
+    }
+}
+
+impl<C> Clock for RedisClock<C>
+where
+    C: redis::ConnectionLike,
+{
+    type Instant = RedisInstant;
+
+    fn now(&self) -> Self::Instant {
+        self.try_now().expect(
+            "Redis I/O failed inside governor::Clock::now; use RedisClock::try_now for a fallible path",
+        )
+    }
+
+    fn start(&self) -> Self::Instant {
+        self.try_start().expect(
+            "Redis I/O failed inside governor::Clock::start; use RedisClock::try_start for a fallible path",
+        )
     }
 }
 
@@ -94,6 +136,16 @@ where
 #[derive(Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Debug)]
 pub struct RedisInstant(Nanos);
 
+impl RedisInstant {
+    pub(crate) fn from_nanos(nanos: Nanos) -> Self {
+        Self(nanos)
+    }
+
+    pub(crate) fn nanos(&self) -> u64 {
+        self.0.into()
+    }
+}
+
 impl Add<Nanos> for RedisInstant {
     type Output = RedisInstant;
 
@@ -0,0 +1,192 @@
+//! Redis Cluster support.
+//!
+//! [`RedisGovernor`](crate::RedisGovernor) assumes a single node: its
+//! `WATCH`/`MULTI`/`HSET` triple only avoids `CROSSSLOT` because
+//! [`state::RedisStateStore`](crate::state::RedisStateStore) hash-tags its
+//! keys, which is necessary but not sufficient - the connection itself
+//! also has to be cluster-aware to route commands by slot at all. This
+//! module mirrors the top-level factory, backed by a
+//! [`redis::cluster::ClusterClient`] instead.
+
+use std::borrow::Cow;
+use std::cell::RefCell;
+use std::fmt::{Debug, Formatter};
+use std::hash::Hash;
+use std::marker::PhantomData;
+use std::rc::Rc;
+
+use governor::middleware::NoOpMiddleware;
+use governor::{Quota, RateLimiter};
+use r2d2::{Pool, PooledConnection};
+use redis::{Cmd, RedisResult, Value};
+
+use crate::clock::RedisClock;
+use crate::error::RedisGovernorError;
+use crate::state::{self, RedisStateStore, DEFAULT_MAX_RETRIES};
+use crate::RedisNoOpMiddleware;
+
+pub struct PooledClusterConnection(pub(crate) PooledConnection<redis::cluster::ClusterClient>);
+
+impl redis::ConnectionLike for PooledClusterConnection {
+    fn req_packed_command(&mut self, cmd: &[u8]) -> RedisResult<Value> {
+        self.0.req_packed_command(cmd)
+    }
+
+    fn req_packed_commands(
+        &mut self,
+        cmd: &[u8],
+        offset: usize,
+        count: usize,
+    ) -> RedisResult<Vec<Value>> {
+        self.0.req_packed_commands(cmd, offset, count)
+    }
+
+    fn req_command(&mut self, cmd: &Cmd) -> RedisResult<Value> {
+        self.0.req_command(cmd)
+    }
+
+    fn get_db(&self) -> i64 {
+        self.0.get_db()
+    }
+
+    fn supports_pipelining(&self) -> bool {
+        self.0.supports_pipelining()
+    }
+
+    fn check_connection(&mut self) -> bool {
+        self.0.check_connection()
+    }
+
+    fn is_open(&self) -> bool {
+        self.0.is_open()
+    }
+}
+
+/// An instance of a Governor with a reserved Cluster connection.
+pub struct ClusteredGovernorInstance<K> {
+    _conn: Rc<RefCell<PooledClusterConnection>>,
+    state: RedisStateStore<PooledClusterConnection, K>,
+    clock: RedisClock<PooledClusterConnection>,
+}
+
+impl<K> ClusteredGovernorInstance<K>
+where
+    K: Hash + Eq + Clone + Debug,
+{
+    pub fn state(&self) -> &RedisStateStore<PooledClusterConnection, K> {
+        &self.state
+    }
+
+    pub fn clock(&self) -> &RedisClock<PooledClusterConnection> {
+        &self.clock
+    }
+
+    /// Wipe all of the rate limits for this governor.
+    pub fn wipe(&self) {
+        self.clock().reset_start();
+        self.state().wipe();
+    }
+
+    /// Create a new [`RateLimiter`](governor::RateLimiter) with a given [`Quota`](governor::Quota).
+    pub fn rate_limiter(
+        &self,
+        quota: Quota,
+    ) -> RateLimiter<
+        K,
+        RedisStateStore<PooledClusterConnection, K>,
+        RedisClock<PooledClusterConnection>,
+        RedisNoOpMiddleware,
+    > {
+        RateLimiter::new(quota, self.state().clone(), self.clock())
+    }
+}
+
+/// A governor rate limiter backed by a Redis Cluster, analogous to
+/// [`RedisGovernor`](crate::RedisGovernor) but routing by hash slot.
+#[derive(Clone)]
+pub struct ClusteredRedisGovernor<K> {
+    pool: Pool<redis::cluster::ClusterClient>,
+    replica_pool: Option<Pool<redis::cluster::ClusterClient>>,
+    prefix: Cow<'static, str>,
+    max_retries: usize,
+    key: PhantomData<K>,
+}
+
+impl<K> Debug for ClusteredRedisGovernor<K> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{} [prefix={}]",
+            std::any::type_name::<Self>(),
+            self.prefix
+        )
+    }
+}
+
+impl<K> ClusteredRedisGovernor<K>
+where
+    K: Hash + Eq + Clone + Debug,
+{
+    /// Create a new [`ClusteredRedisGovernor`](Self) for an existing
+    /// [`redis::cluster::ClusterClient`].
+    ///
+    /// See [`RedisGovernor::new`](crate::RedisGovernor::new) for the
+    /// meaning of `prefix`.
+    pub fn new<I>(client: redis::cluster::ClusterClient, prefix: I) -> Result<Self, RedisGovernorError>
+    where
+        I: Into<Cow<'static, str>>,
+    {
+        let prefix = prefix.into();
+
+        Ok(Self {
+            pool: r2d2::Pool::builder().build(client)?,
+            replica_pool: None,
+            prefix,
+            max_retries: DEFAULT_MAX_RETRIES,
+            key: Default::default(),
+        })
+    }
+
+    /// See [`RedisGovernor::with_max_retries`](crate::RedisGovernor::with_max_retries).
+    pub fn with_max_retries(mut self, max_retries: usize) -> Self {
+        self.max_retries = max_retries;
+        self
+    }
+
+    /// Route every [`RedisClock::now`](crate::clock::RedisClock::now)
+    /// `TIME` read, for instances handed out from this point on, over a
+    /// separate pool of connections to `replica_client` instead of the
+    /// primary pool.
+    ///
+    /// `replica_client` must already be configured to route its reads to
+    /// Cluster replicas (e.g. via
+    /// `redis::cluster::ClusterClientBuilder::read_from_replicas`) - this
+    /// only keeps a second pool of connections to it around for `now()`
+    /// to borrow from. Every write, and the `start` CAS, always go
+    /// through the primary pool regardless.
+    pub fn with_replica_client(
+        mut self,
+        replica_client: redis::cluster::ClusterClient,
+    ) -> Result<Self, RedisGovernorError> {
+        self.replica_pool = Some(r2d2::Pool::builder().build(replica_client)?);
+        Ok(self)
+    }
+
+    pub fn instance(&self) -> Result<ClusteredGovernorInstance<K>, RedisGovernorError> {
+        let conn = Rc::new(RefCell::new(PooledClusterConnection(self.pool.get()?)));
+
+        let mut clock = RedisClock::new(conn.clone(), &self.prefix);
+        if let Some(replica_pool) = &self.replica_pool {
+            let replica_conn = Rc::new(RefCell::new(PooledClusterConnection(
+                replica_pool.get()?,
+            )));
+            clock = clock.with_replica_conn(replica_conn);
+        }
+
+        Ok(ClusteredGovernorInstance {
+            _conn: conn.clone(),
+            state: state::RedisStateStore::new(conn, self.prefix.clone(), self.max_retries),
+            clock,
+        })
+    }
+}
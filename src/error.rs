@@ -0,0 +1,67 @@
+use std::fmt;
+
+/// Errors produced by `redis-governor`'s own fallible operations.
+///
+/// This doesn't cover every Redis round trip in the crate: `governor`'s
+/// [`Clock`](governor::clock::Clock) and
+/// [`StateStore`](governor::state::StateStore) traits fix
+/// [`RedisClock::now`](crate::clock::RedisClock)/`start` to return
+/// `Self::Instant`, and [`RedisStateStore::measure_and_replace`](crate::state::RedisStateStore)
+/// to return the caller-supplied decision error, so neither has anywhere
+/// to put a connection failure. This type covers the operations that
+/// aren't constrained that way: connecting, pooling, the `try_*` twins
+/// of those trait methods, the scripted and batched checks, and bounding
+/// the check-and-set retry loop.
+#[derive(Debug)]
+pub enum RedisGovernorError {
+    /// Failed to open or borrow a connection to Redis, or a command
+    /// failed for any reason other than a type mismatch in its reply
+    /// (a dropped connection, an error reply from the server, ...).
+    Connection(redis::RedisError),
+    /// A value read back from Redis wasn't shaped the way this crate
+    /// expects (e.g. a TAT entry that isn't the `u64` this crate wrote,
+    /// or a script reply that isn't the expected pair). Distinct
+    /// from [`Connection`](Self::Connection) because the round trip
+    /// itself succeeded - the stored data, not the connection, is the
+    /// problem.
+    Serialization(redis::RedisError),
+    /// The connection pool had no connections available.
+    PoolExhausted(r2d2::Error),
+    /// The WATCH/MULTI check-and-set loop kept losing the race against
+    /// concurrent writers past the configured retry bound.
+    MaxRetriesExceeded { attempts: usize },
+}
+
+impl fmt::Display for RedisGovernorError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Connection(e) => write!(f, "failed to connect to Redis: {}", e),
+            Self::Serialization(e) => write!(f, "failed to decode a value read from Redis: {}", e),
+            Self::PoolExhausted(e) => write!(f, "failed to obtain a pooled connection: {}", e),
+            Self::MaxRetriesExceeded { attempts } => write!(
+                f,
+                "gave up on the check-and-set loop after {} attempts without winning",
+                attempts
+            ),
+        }
+    }
+}
+
+impl std::error::Error for RedisGovernorError {}
+
+impl From<redis::RedisError> for RedisGovernorError {
+    fn from(e: redis::RedisError) -> Self {
+        use redis::ErrorKind;
+
+        match e.kind() {
+            ErrorKind::TypeError => Self::Serialization(e),
+            _ => Self::Connection(e),
+        }
+    }
+}
+
+impl From<r2d2::Error> for RedisGovernorError {
+    fn from(e: r2d2::Error) -> Self {
+        Self::PoolExhausted(e)
+    }
+}
@@ -0,0 +1,108 @@
+use governor::Quota;
+
+/// The two time constants GCRA needs, derived once from a [`Quota`].
+///
+/// `governor`'s own [`RateLimiter`](governor::RateLimiter) makes this same
+/// decision internally for the synchronous, closure-based
+/// [`StateStore`](governor::state::StateStore) path. Anything that has to
+/// make the admit/deny call itself instead of going through a `RateLimiter`
+/// needs the same constants and the same comparison: the batch check in
+/// [`GovernorInstance::check_n`](crate::GovernorInstance::check_n) uses
+/// both from here, while the Lua scripts in [`crate::script`] and
+/// [`crate::aio`] take the constants from here and repeat the comparison
+/// server-side.
+#[derive(Copy, Clone, Debug)]
+pub(crate) struct GcraParameters {
+    /// Nanoseconds that must elapse between two single-cell arrivals at
+    /// the quota's sustained rate.
+    pub(crate) emission_interval_nanos: u64,
+    /// Nanoseconds the theoretical arrival time is allowed to run ahead
+    /// of `now` before a cell is rejected (the total burst tolerance).
+    pub(crate) delay_variation_tolerance_nanos: u64,
+    /// The burst size the above were derived from.
+    pub(crate) burst: u64,
+}
+
+impl GcraParameters {
+    pub(crate) fn from_quota(quota: &Quota) -> Self {
+        let burst = quota.max_burst().get() as u64;
+        let emission_interval_nanos = quota.replenish_interval().as_nanos() as u64;
+        let delay_variation_tolerance_nanos = emission_interval_nanos * burst;
+
+        Self {
+            emission_interval_nanos,
+            delay_variation_tolerance_nanos,
+            burst,
+        }
+    }
+
+    /// Decide whether a single cell arriving at `now_nanos` should be
+    /// admitted, given the previously stored theoretical arrival time
+    /// (`None` if the key has never been seen before).
+    ///
+    /// Returns the new `tat` to persist on success, or the nanoseconds
+    /// until the next attempt would succeed on failure.
+    pub(crate) fn decide(&self, prev_tat_nanos: Option<u64>, now_nanos: u64) -> Result<u64, u64> {
+        self.decide_n(prev_tat_nanos, now_nanos, 1)
+    }
+
+    /// Same as [`decide`](Self::decide), but for `n` cells arriving at
+    /// once instead of a single one.
+    pub(crate) fn decide_n(
+        &self,
+        prev_tat_nanos: Option<u64>,
+        now_nanos: u64,
+        n: u64,
+    ) -> Result<u64, u64> {
+        let tat = prev_tat_nanos.unwrap_or(now_nanos);
+        let new_tat = tat.max(now_nanos) + self.emission_interval_nanos * n;
+        let allow_at = new_tat.saturating_sub(self.delay_variation_tolerance_nanos);
+
+        if allow_at > now_nanos {
+            Err(allow_at - now_nanos)
+        } else {
+            Ok(new_tat)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::num::NonZeroU32;
+    use std::time::Duration;
+
+    #[test]
+    fn sustained_rate_matches_replenish_interval() {
+        // 10/s should admit one cell every 100ms at steady state, not
+        // `burst` times that - the invariant the chunk0-1 bug broke by
+        // dividing the emission interval by burst.
+        let quota = Quota::per_second(NonZeroU32::new(10).unwrap());
+        let params = GcraParameters::from_quota(&quota);
+
+        assert_eq!(
+            params.emission_interval_nanos,
+            Duration::from_millis(100).as_nanos() as u64
+        );
+        assert_eq!(
+            params.delay_variation_tolerance_nanos,
+            params.emission_interval_nanos * 10
+        );
+    }
+
+    #[test]
+    fn decide_admits_exactly_burst_cells_then_rejects() {
+        let quota = Quota::per_second(NonZeroU32::new(10).unwrap());
+        let params = GcraParameters::from_quota(&quota);
+
+        let mut tat = None;
+        for _ in 0..10 {
+            tat = Some(params.decide(tat, 0).expect("should admit within burst"));
+        }
+
+        // The bug let a full extra `burst` worth of cells land in the
+        // same instant, since the emission interval was `burst` times
+        // too small.
+        assert!(params.decide(tat, 0).is_err());
+    }
+}
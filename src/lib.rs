@@ -7,44 +7,53 @@ use std::fmt::{Debug, Formatter};
 use std::hash::Hash;
 use std::marker::PhantomData;
 use std::rc::Rc;
+use std::time::Duration;
 
 pub use governor;
 
 use governor::middleware::NoOpMiddleware;
+use governor::nanos::Nanos;
 use governor::{Quota, RateLimiter};
 use r2d2::{Pool, PooledConnection};
 use redis::{Cmd, RedisResult, Value};
 
-#[macro_use]
-mod private_macros {
-    macro_rules! redis_check_and_set {
-        ($conn:expr, ($watch_key:expr) => $block:expr) => {
-            loop {
-                // WATCH value field
-                // This will abort the atomic section later if the semaphore key is updated
-                // by another connection.
-                // WATCHes are always cancelled after an EXEC command, so it needs
-                // to be performed every iteration.
-                let _: () = redis::cmd("WATCH")
-                    .arg($watch_key)
-                    .query($conn)
-                    .expect("Failed to watch for key");
-
-                {
-                    $block
-                }
-            }
-        };
-    }
-}
+use gcra::GcraParameters;
 
+pub mod aio;
 pub mod clock;
+pub mod cluster;
+pub mod error;
+mod gcra;
+pub mod script;
 pub mod state;
 
+pub use error::RedisGovernorError;
+
 /// A [`NoOpMiddleware`](governor::middleware::NoOpMiddleware) usable when using
 /// [`RedisClock`](crate::clock::RedisClock) as a clock source.
 pub type RedisNoOpMiddleware = NoOpMiddleware<clock::RedisInstant>;
 
+/// Returned by [`GovernorInstance::check_keys`]/[`check_n`](GovernorInstance::check_n)
+/// when a cell is rejected.
+///
+/// Unlike [`governor::NotUntil`], this is built directly from the GCRA
+/// decision made against Redis rather than from a running
+/// [`governor::RateLimiter`], since the batch path makes its own
+/// admit/deny decision instead of going through one (the same reason
+/// [`aio::AsyncNotUntil`](aio::AsyncNotUntil) and
+/// [`script::NotUntil`](script::NotUntil) exist).
+#[derive(Copy, Clone, Debug)]
+pub struct NotUntil {
+    retry_after_nanos: u64,
+}
+
+impl NotUntil {
+    /// How long the caller should wait before retrying.
+    pub fn wait_time(&self) -> Duration {
+        Duration::from_nanos(self.retry_after_nanos)
+    }
+}
+
 pub struct PooledRedisConnection(pub(crate) PooledConnection<redis::Client>);
 
 impl redis::ConnectionLike for PooledRedisConnection {
@@ -122,6 +131,60 @@ where
     {
         RateLimiter::new(quota, self.state().clone(), self.clock())
     }
+
+    /// Create a [`GcraScriptLimiter`](script::GcraScriptLimiter) for a
+    /// given [`Quota`](governor::Quota).
+    ///
+    /// Unlike [`rate_limiter`](Self::rate_limiter), this makes its GCRA
+    /// decision in a single server-side `EVALSHA` instead of a
+    /// client-side WATCH/MULTI retry loop - see [`script`] for the
+    /// tradeoffs.
+    pub fn scripted_rate_limiter(&self, quota: Quota) -> script::GcraScriptLimiter<C, K> {
+        script::GcraScriptLimiter::new(
+            self._conn.clone(),
+            self.clock.clone(),
+            self.state().prefix.clone(),
+            quota,
+        )
+    }
+
+    /// Check many distinct keys against `quota` in a fixed number of
+    /// round trips per attempt (one `TIME`, one `WATCH`, one pipelined
+    /// batch of `HGET`s and one pipelined commit), instead of one
+    /// `check_key` - and one WATCH/HGET/MULTI - per key.
+    ///
+    /// Like [`scripted_rate_limiter`](Self::scripted_rate_limiter), this
+    /// makes its own GCRA decision against [`state()`](Self::state)
+    /// rather than going through a [`governor::RateLimiter`] (which only
+    /// ever checks one key per call). Results line up with `keys` by
+    /// index.
+    pub fn check_keys(
+        &self,
+        keys: &[K],
+        quota: Quota,
+    ) -> Result<Vec<Result<(), NotUntil>>, RedisGovernorError> {
+        self.check_n(keys, quota, 1)
+    }
+
+    /// Same as [`check_keys`](Self::check_keys), but each key consumes
+    /// `n` cells of quota instead of 1.
+    pub fn check_n(
+        &self,
+        keys: &[K],
+        quota: Quota,
+        n: u32,
+    ) -> Result<Vec<Result<(), NotUntil>>, RedisGovernorError> {
+        let params = GcraParameters::from_quota(&quota);
+        let now_nanos = self.clock.try_now()?.nanos();
+
+        self.state.try_measure_and_replace_many(keys, |_key, prev| {
+            let prev_nanos = prev.map(u64::from);
+            match params.decide_n(prev_nanos, now_nanos, n as u64) {
+                Ok(new_tat) => Ok(((), Nanos::new(new_tat))),
+                Err(retry_after_nanos) => Err(NotUntil { retry_after_nanos }),
+            }
+        })
+    }
 }
 
 /// A governor rate limiter using Redis as a distributed store.
@@ -132,6 +195,7 @@ where
 pub struct RedisGovernor<K> {
     pool: Pool<redis::Client>,
     prefix: Cow<'static, str>,
+    max_retries: usize,
     key: PhantomData<K>,
 }
 
@@ -156,28 +220,42 @@ where
     /// the governor (e.g. different services sharing a Redis instance)
     /// to prevent key collisions. The `prefix` will be cloned onto the heap if it
     /// is not a compile-time static string.
-    pub fn new<I>(client: redis::Client, prefix: I) -> Self
+    ///
+    /// Fails if the underlying r2d2 pool can't be built (e.g. the client's
+    /// connection info can't be resolved).
+    pub fn new<I>(client: redis::Client, prefix: I) -> Result<Self, RedisGovernorError>
     where
         I: Into<Cow<'static, str>>,
     {
         let prefix = prefix.into();
 
-        Self {
-            pool: r2d2::Pool::builder().build(client).unwrap(),
+        Ok(Self {
+            pool: r2d2::Pool::builder().build(client)?,
             prefix,
+            max_retries: state::DEFAULT_MAX_RETRIES,
             key: Default::default(),
-        }
+        })
     }
 
-    pub fn instance(&self) -> GovernorInstance<PooledRedisConnection, K> {
-        let conn = Rc::new(RefCell::new(PooledRedisConnection(
-            self.pool.get().unwrap(),
-        )));
+    /// Override the check-and-set retry bound used by every
+    /// [`GovernorInstance`] handed out from this point on.
+    ///
+    /// See [`RedisGovernorError::MaxRetriesExceeded`].
+    pub fn with_max_retries(mut self, max_retries: usize) -> Self {
+        self.max_retries = max_retries;
+        self
+    }
+
+    /// Reserve a connection from the pool and build a [`GovernorInstance`].
+    ///
+    /// Fails if the pool has no connections available to hand out.
+    pub fn instance(&self) -> Result<GovernorInstance<PooledRedisConnection, K>, RedisGovernorError> {
+        let conn = Rc::new(RefCell::new(PooledRedisConnection(self.pool.get()?)));
 
-        GovernorInstance {
+        Ok(GovernorInstance {
             _conn: conn.clone(),
-            state: state::RedisStateStore::new(conn.clone(), self.prefix.clone()),
+            state: state::RedisStateStore::new(conn.clone(), self.prefix.clone(), self.max_retries),
             clock: clock::RedisClock::new(conn, &self.prefix),
-        }
+        })
     }
 }
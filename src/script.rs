@@ -0,0 +1,211 @@
+use std::borrow::Cow;
+use std::cell::RefCell;
+use std::hash::{Hash, Hasher};
+use std::marker::PhantomData;
+use std::rc::Rc;
+use std::time::Duration;
+
+use governor::Quota;
+use redis::Script;
+use siphasher::sip::SipHasher;
+
+use crate::clock::RedisClock;
+use crate::error::RedisGovernorError;
+use crate::gcra::GcraParameters;
+
+/// The GCRA decision itself, run server-side.
+///
+/// Mirrors [`GcraParameters::decide`](crate::gcra::GcraParameters::decide):
+/// `KEYS[1]` is the per-key TAT entry, `ARGV` is
+/// `{now_nanos, emission_interval_nanos, delay_variation_tolerance_nanos, burst, n}`,
+/// where `n` is the number of cells this call is asking for (1 for a
+/// plain [`check_key`](GcraScriptLimiter::check_key)). Returns `{1, 0}` on
+/// success (having already stored the new TAT with a TTL of `dvt`, so idle
+/// keys expire) or `{0, retry_after_nanos}` on rejection.
+///
+/// Lua only has one number type, an IEEE 754 double: its 53-bit mantissa
+/// represents nanosecond epoch timestamps (currently ~1.75e18, past
+/// 2^53 ~9e15) to roughly 256ns granularity rather than exactly. That's
+/// invisible at the second/minute quotas this crate targets, but a quota
+/// relying on sub-microsecond spacing between cells would see its
+/// `emission_interval`/`dvt` math silently rounded here in a way the
+/// full-`u64` comparison in [`crate::gcra`] never rounds.
+pub(crate) const GCRA_SCRIPT: &str = r#"
+local tat_key = KEYS[1]
+local now = tonumber(ARGV[1])
+local emission_interval = tonumber(ARGV[2])
+local dvt = tonumber(ARGV[3])
+-- ARGV[4] (burst) isn't needed for the comparison itself - dvt already
+-- encodes it - but is passed through for parity with the client-side
+-- GcraParameters this mirrors.
+local n = tonumber(ARGV[5])
+
+local tat = tonumber(redis.call('GET', tat_key))
+if not tat then
+    tat = now
+end
+
+local new_tat = math.max(tat, now) + (emission_interval * n)
+local allow_at = new_tat - dvt
+
+if allow_at > now then
+    return {0, allow_at - now}
+end
+
+redis.call('SET', tat_key, new_tat, 'PX', math.ceil(dvt / 1e6))
+return {1, 0}
+"#;
+
+/// Returned by [`GcraScriptLimiter::check_key`] when a cell is rejected.
+#[derive(Copy, Clone, Debug)]
+pub struct NotUntil {
+    retry_after_nanos: u64,
+}
+
+impl NotUntil {
+    /// How long the caller should wait before retrying.
+    pub fn wait_time(&self) -> Duration {
+        Duration::from_nanos(self.retry_after_nanos)
+    }
+}
+
+/// A GCRA rate limiter that makes its admit/deny decision inside a single
+/// `EVALSHA` (loading the script and retrying on `NOSCRIPT` - handled
+/// internally by [`redis::Script`]), instead of the client-side WATCH/MULTI
+/// check-and-set loop [`RedisStateStore`](crate::state::RedisStateStore)
+/// uses.
+///
+/// Tied to one [`Quota`] at construction, same as
+/// [`GovernorInstance::rate_limiter`](crate::GovernorInstance::rate_limiter):
+/// the emission interval and delay variation tolerance are derived once
+/// and baked into every `EVALSHA` call rather than being recomputed.
+pub struct GcraScriptLimiter<C, K> {
+    conn: Rc<RefCell<C>>,
+    clock: RedisClock<C>,
+    prefix: Cow<'static, str>,
+    params: GcraParameters,
+    script: Script,
+    key: PhantomData<K>,
+}
+
+impl<C, K> GcraScriptLimiter<C, K> {
+    pub(crate) fn new<I: Into<Cow<'static, str>>>(
+        conn: Rc<RefCell<C>>,
+        clock: RedisClock<C>,
+        prefix: I,
+        quota: Quota,
+    ) -> Self {
+        Self {
+            conn,
+            clock,
+            prefix: prefix.into(),
+            params: GcraParameters::from_quota(&quota),
+            script: Script::new(GCRA_SCRIPT),
+            key: Default::default(),
+        }
+    }
+}
+
+impl<C, K> GcraScriptLimiter<C, K>
+where
+    C: redis::ConnectionLike,
+    K: Hash,
+{
+    fn tat_key(&self, key: &K) -> String {
+        let mut hasher = SipHasher::new();
+        key.hash(&mut hasher);
+        // Same hash-tag scheme as `RedisStateStore`, so this limiter's
+        // keys land in the same Cluster slot as the rest of the
+        // governor's keys for this prefix.
+        format!("{{{}}}:governor:value:{:x}", self.prefix, hasher.finish())
+    }
+
+    /// Check `key` against the quota this limiter was built with: one
+    /// `TIME` read, then the decision in a single atomic `EVALSHA`.
+    pub fn check_key(&self, key: &K) -> Result<Result<(), NotUntil>, RedisGovernorError> {
+        self.check_key_n(key, 1)
+    }
+
+    /// Check `key` for `n` cells at once - e.g. a request that should
+    /// consume several units of quota - with one `TIME` read and a single
+    /// atomic `EVALSHA`.
+    pub fn check_key_n(
+        &self,
+        key: &K,
+        n: u32,
+    ) -> Result<Result<(), NotUntil>, RedisGovernorError> {
+        let now_nanos = self.clock.try_now()?.nanos();
+
+        let (allowed, retry_after_nanos): (i64, i64) = self
+            .script
+            .key(self.tat_key(key))
+            .arg(now_nanos)
+            .arg(self.params.emission_interval_nanos)
+            .arg(self.params.delay_variation_tolerance_nanos)
+            .arg(self.params.burst)
+            .arg(n)
+            .invoke(&mut *self.conn.borrow_mut())?;
+
+        Ok(Self::decode(allowed, retry_after_nanos))
+    }
+
+    /// Check many distinct keys against the quota this limiter was built
+    /// with, coalescing all of their `EVALSHA` calls into a single
+    /// pipelined round trip.
+    ///
+    /// Results line up with `keys` by index.
+    pub fn check_keys(
+        &self,
+        keys: &[K],
+    ) -> Result<Vec<Result<(), NotUntil>>, RedisGovernorError> {
+        let now_nanos = self.clock.try_now()?.nanos();
+        let mut conn = self.conn.borrow_mut();
+
+        let build_pipe = |hash: &str| {
+            let mut pipe = redis::pipe();
+            for key in keys {
+                pipe.cmd("EVALSHA")
+                    .arg(hash)
+                    .arg(1)
+                    .arg(self.tat_key(key))
+                    .arg(now_nanos)
+                    .arg(self.params.emission_interval_nanos)
+                    .arg(self.params.delay_variation_tolerance_nanos)
+                    .arg(self.params.burst)
+                    .arg(1u32);
+            }
+            pipe
+        };
+
+        let replies: Vec<(i64, i64)> = match build_pipe(self.script.get_hash()).query(&mut *conn)
+        {
+            Ok(replies) => replies,
+            // The script isn't cached on this node yet: load it once and
+            // retry the whole batch, rather than falling all the way
+            // back to one EVAL per key.
+            Err(e) if e.kind() == redis::ErrorKind::NoScriptError => {
+                let _: String = redis::cmd("SCRIPT")
+                    .arg("LOAD")
+                    .arg(GCRA_SCRIPT)
+                    .query(&mut *conn)?;
+                build_pipe(self.script.get_hash()).query(&mut *conn)?
+            }
+            Err(e) => return Err(e.into()),
+        };
+
+        Ok(replies
+            .into_iter()
+            .map(|(allowed, retry_after_nanos)| Self::decode(allowed, retry_after_nanos))
+            .collect())
+    }
+
+    fn decode(allowed: i64, retry_after_nanos: i64) -> Result<(), NotUntil> {
+        if allowed == 1 {
+            Ok(())
+        } else {
+            Err(NotUntil {
+                retry_after_nanos: retry_after_nanos as u64,
+            })
+        }
+    }
+}
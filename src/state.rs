@@ -11,6 +11,12 @@ use std::ops::{Deref, DerefMut};
 
 use std::rc::Rc;
 
+use crate::error::RedisGovernorError;
+
+/// Default bound on check-and-set retries, used unless overridden with
+/// [`RedisGovernor::with_max_retries`](crate::RedisGovernor::with_max_retries).
+pub(crate) const DEFAULT_MAX_RETRIES: usize = 100;
+
 /// Governor state store backed by a Redis instance
 ///
 /// The state store uses a single Redis hash as the canonical
@@ -21,6 +27,7 @@ pub struct RedisStateStore<C, K> {
     conn: Rc<RefCell<C>>,
     pub(crate) prefix: Cow<'static, str>,
     hash_key: String,
+    max_retries: usize,
     key: PhantomData<K>,
 }
 
@@ -33,6 +40,7 @@ impl<C, K> Clone for RedisStateStore<C, K> {
             conn: self.conn.clone(),
             prefix: self.prefix.clone(),
             hash_key: self.hash_key.clone(),
+            max_retries: self.max_retries,
             key: Default::default(),
         }
     }
@@ -42,12 +50,21 @@ impl<C, K> RedisStateStore<C, K>
 where
     C: redis::ConnectionLike,
 {
-    pub(crate) fn new<I: Into<Cow<'static, str>>>(conn: Rc<RefCell<C>>, prefix: I) -> Self {
+    pub(crate) fn new<I: Into<Cow<'static, str>>>(
+        conn: Rc<RefCell<C>>,
+        prefix: I,
+        max_retries: usize,
+    ) -> Self {
         let prefix = prefix.into();
         Self {
             conn,
-            hash_key: format!("{}:governor:hash", prefix),
+            // Hash-tagged so the hash key and every per-key value key
+            // below land in the same Redis Cluster slot: only the
+            // brace-delimited `{prefix}` portion is hashed to pick a
+            // slot, so `CROSSSLOT` can't happen between them.
+            hash_key: format!("{{{}}}:governor:hash", prefix),
             prefix,
+            max_retries,
             key: Default::default(),
         }
     }
@@ -65,84 +82,30 @@ impl<C, K: Hash> RedisStateStore<C, K> {
     }
 }
 
-impl<C, K> StateStore for RedisStateStore<C, K>
+impl<C, K> RedisStateStore<C, K>
 where
     C: redis::ConnectionLike,
     K: Hash + Eq + Clone + Debug,
-    // {
-    //     type Key = K;
-    //
-    //     fn measure_and_replace<T, F, E>(&self, key: &Self::Key, f: F) -> Result<T, E>
-    //     where
-    //         F: Fn(Option<Nanos>) -> Result<(T, Nanos), E>,
-    //     {
-    //         trace!("Measure and replace for {:?}", key);
-    //         let hash = self.key_hash(key);
-    //         // We need a separate individual value key, as Redis can't WATCH a HASH field,
-    //         // but equally finding all the values with a given prefix is O(n),
-    //         // whereas it's O(1) for a Hash.
-    //         let value_key = format!("{}:governor:value:{}", self.prefix, &hash);
-    //
-    //         let conn = &mut *self.conn.borrow_mut();
-    //
-    //         // This loop will effectively attempt to set the Redis key
-    //         // by doing check-and-set attempts until it "wins", similar to
-    //         // reference implementations used in governor.
-    //         loop {
-    //             trace!("Check and set attempt");
-    //             // WATCH value field
-    //             // This will abort the atomic section later if the semaphore key is updated
-    //             // by another connection.
-    //             // WATCHes are always cancelled after an EXEC command, so it needs
-    //             // to be performed every iteration.
-    //             let _: () = redis::cmd("WATCH")
-    //                 .arg(&value_key)
-    //                 .query(conn)
-    //                 .expect("Failed to watch for key");
-    //
-    //             // Obtain previous value from state store.
-    //             let prev: Option<u64> = redis::Cmd::hget(&self.hash_key, &hash)
-    //                 .query(conn)
-    //                 .expect("Failed to check Redis for key presence");
-    //             trace!("Previous value: {:?}", prev);
-    //             let decision = f(prev.map(Into::into));
-    //
-    //             match decision {
-    //                 Ok((result, new_data)) => {
-    //                     trace!("Updated, setting data to {:?}", new_data);
-    //                     // The atomic block sets the value key to trigger the semaphore
-    //                     // and then HSETs the store key in the hashtable which is used
-    //                     // as the actual store
-    //                     let new_data: u64 = new_data.into();
-    //                     let response: Option<()> = redis::pipe()
-    //                         .atomic()
-    //                         .set(&value_key, new_data)
-    //                         .ignore()
-    //                         .hset(&self.hash_key, &hash, new_data)
-    //                         .query(conn)
-    //                         .expect("Failed to run atomic section");
-    //
-    //                     match response {
-    //                         // The request was successful
-    //                         Some(()) => return Ok(result),
-    //                         None => {
-    //                             trace!("Key update conflict for {:?}, retrying", key);
-    //                             continue;
-    //                         }
-    //                     }
-    //                 }
-    //                 Err(_) => {
-    //                     trace!("Error setting key");
-    //                     return decision.map(|(result, _)| result);
-    //                 }
-    //             }
-    //         }
-    //     }
-    // }
 {
-    type Key = K;
-
-    fn measure_and_replace<T, F, E>(&self, key: &Self::Key, f: F) -> Result<T, E>
+    /// Same check-and-set loop as the [`StateStore`] impl below, but
+    /// surfaces a failed Redis round trip (or a check-and-set loop that
+    /// keeps losing past `max_retries`) as `Err(RedisGovernorError)`
+    /// instead of panicking.
+    ///
+    /// `StateStore::measure_and_replace` fixes its return type to
+    /// `Result<T, E>`, where `E` is the decision closure's own error -
+    /// there's no room in that signature for a distinct "Redis is down"
+    /// case, and an impl can't add an `E: From<RedisGovernorError>`
+    /// bound the trait doesn't declare (that's rustc's E0276). Callers
+    /// who need to fail open/closed on a Redis outage, rather than
+    /// crash, should call this directly instead of going through
+    /// `governor::RateLimiter`; the trait impl below calls this and
+    /// panics at that one, documented boundary.
+    pub fn try_measure_and_replace<T, F, E>(
+        &self,
+        key: &K,
+        f: F,
+    ) -> Result<Result<T, E>, RedisGovernorError>
     where
         F: Fn(Option<Nanos>) -> Result<(T, Nanos), E>,
     {
@@ -151,17 +114,36 @@ where
         // We need a separate individual value key, as Redis can't WATCH a HASH field,
         // but equally finding all the values with a given prefix is O(n),
         // whereas it's O(1) for a Hash.
-        let value_key = format!("{}:governor:value:{}", self.prefix, &hash);
+        //
+        // Hash-tagged with the same `{prefix}` as `hash_key` above so the
+        // WATCH, the MULTI pipeline, and the HSET all land on the same
+        // Cluster slot.
+        let value_key = format!("{{{}}}:governor:value:{}", self.prefix, &hash);
 
         // This loop will effectively attempt to set the Redis key
         // by doing check-and-set attempts until it "wins", similar to
         // reference implementations used in governor.
         let mut conn = self.conn.deref().borrow_mut();
-        redis_check_and_set!(conn.deref_mut(), (&value_key) => {
+        let mut attempts = 0usize;
+        loop {
+            trace!("Check and set attempt");
+            attempts += 1;
+            if attempts > self.max_retries {
+                return Err(RedisGovernorError::MaxRetriesExceeded {
+                    attempts: self.max_retries,
+                });
+            }
+
+            // WATCH value field
+            // This will abort the atomic section later if the semaphore key is updated
+            // by another connection.
+            // WATCHes are always cancelled after an EXEC command, so it needs
+            // to be performed every iteration.
+            let _: () = redis::cmd("WATCH").arg(&value_key).query(conn.deref_mut())?;
+
             // Obtain previous value from state store.
-            let prev: Option<u64> = redis::Cmd::hget(&self.hash_key, &hash)
-                .query(conn.deref_mut())
-                .expect("Failed to check Redis for key presence");
+            let prev: Option<u64> =
+                redis::Cmd::hget(&self.hash_key, &hash).query(conn.deref_mut())?;
             trace!("Previous value: {:?}", prev);
             let decision = f(prev.map(Into::into));
 
@@ -177,23 +159,133 @@ where
                         .set(&value_key, new_data)
                         .ignore()
                         .hset(&self.hash_key, &hash, new_data)
-                        .query(conn.deref_mut())
-                        .expect("Failed to run atomic section");
+                        .query(conn.deref_mut())?;
 
                     match response {
                         // The request was successful
-                        Some(()) => return Ok(result),
+                        Some(()) => return Ok(Ok(result)),
                         None => {
                             trace!("Key update conflict for {:?}, retrying", key);
                             continue;
                         }
                     }
                 }
-                Err(_) => {
+                Err(e) => {
                     trace!("Error setting key");
-                    return decision.map(|(result, _)| result);
+                    return Ok(Err(e));
                 }
             }
-        })
+        }
+    }
+
+    /// Batched counterpart of [`try_measure_and_replace`](Self::try_measure_and_replace):
+    /// checks many keys in three round trips per attempt by `WATCH`ing
+    /// all of their value keys together and pipelining the `HGET`s and
+    /// the final commit, instead of one WATCH/HGET/MULTI per key.
+    ///
+    /// `f` is called once per key with that key's previous value. A
+    /// conflicting write on any watched key retries the whole batch, the
+    /// same way a single conflicting write retries
+    /// `try_measure_and_replace` - the key layout and hashing are
+    /// otherwise identical.
+    ///
+    /// Results line up with `keys` by index.
+    pub fn try_measure_and_replace_many<T, F, E>(
+        &self,
+        keys: &[K],
+        f: F,
+    ) -> Result<Vec<Result<T, E>>, RedisGovernorError>
+    where
+        F: Fn(&K, Option<Nanos>) -> Result<(T, Nanos), E>,
+    {
+        if keys.is_empty() {
+            return Ok(Vec::new());
+        }
+
+        let hashes: Vec<String> = keys.iter().map(|key| self.key_hash(key)).collect();
+        let value_keys: Vec<String> = hashes
+            .iter()
+            .map(|hash| format!("{{{}}}:governor:value:{}", self.prefix, hash))
+            .collect();
+
+        let mut conn = self.conn.deref().borrow_mut();
+        let mut attempts = 0usize;
+        loop {
+            trace!("Check and set attempt for {} keys", keys.len());
+            attempts += 1;
+            if attempts > self.max_retries {
+                return Err(RedisGovernorError::MaxRetriesExceeded {
+                    attempts: self.max_retries,
+                });
+            }
+
+            let mut watch = redis::cmd("WATCH");
+            for value_key in &value_keys {
+                watch.arg(value_key);
+            }
+            let _: () = watch.query(conn.deref_mut())?;
+
+            let mut hget_pipe = redis::pipe();
+            for hash in &hashes {
+                hget_pipe.cmd("HGET").arg(&self.hash_key).arg(hash);
+            }
+            let prevs: Vec<Option<u64>> = hget_pipe.query(conn.deref_mut())?;
+
+            let mut results = Vec::with_capacity(keys.len());
+            let mut commit_pipe = redis::pipe();
+            commit_pipe.atomic();
+            let mut any_ok = false;
+
+            for ((key, hash), prev) in keys.iter().zip(hashes.iter()).zip(prevs) {
+                match f(key, prev.map(Into::into)) {
+                    Ok((result, new_data)) => {
+                        any_ok = true;
+                        let new_data: u64 = new_data.into();
+                        let value_key = format!("{{{}}}:governor:value:{}", self.prefix, hash);
+                        commit_pipe
+                            .set(&value_key, new_data)
+                            .ignore()
+                            .hset(&self.hash_key, hash, new_data)
+                            .ignore();
+                        results.push(Ok(result));
+                    }
+                    Err(e) => results.push(Err(e)),
+                }
+            }
+
+            if !any_ok {
+                // Nothing to commit - no point opening a MULTI just to
+                // immediately abort it.
+                let _: () = redis::cmd("UNWATCH").query(conn.deref_mut())?;
+                return Ok(results);
+            }
+
+            let response: Option<()> = commit_pipe.query(conn.deref_mut())?;
+            match response {
+                Some(()) => return Ok(results),
+                None => {
+                    trace!("Key update conflict in batch, retrying");
+                    continue;
+                }
+            }
+        }
+    }
+}
+
+impl<C, K> StateStore for RedisStateStore<C, K>
+where
+    C: redis::ConnectionLike,
+    K: Hash + Eq + Clone + Debug,
+{
+    type Key = K;
+
+    fn measure_and_replace<T, F, E>(&self, key: &Self::Key, f: F) -> Result<T, E>
+    where
+        F: Fn(Option<Nanos>) -> Result<(T, Nanos), E>,
+    {
+        self.try_measure_and_replace(key, f).expect(
+            "Redis I/O failed inside governor::StateStore::measure_and_replace; \
+             use RedisStateStore::try_measure_and_replace for a fallible path",
+        )
     }
 }
@@ -45,8 +45,11 @@ fn rate_limiter_works() {
     let redis = redis::Client::open("redis://127.0.0.1/").unwrap();
     let quota = fixed_quota(LIMIT);
 
-    let pooled_governor = RedisGovernor::new(redis, "basic-rate-limiter-test");
-    let governor = pooled_governor.instance();
+    let pooled_governor = RedisGovernor::new(redis, "basic-rate-limiter-test")
+        .expect("failed to create governor");
+    let governor = pooled_governor
+        .instance()
+        .expect("failed to reserve connection");
     governor.wipe();
 
     let redis_limiter = governor.rate_limiter(quota);
@@ -65,8 +68,11 @@ fn rate_limiter_can_recover() {
     let redis = redis::Client::open("redis://127.0.0.1/").unwrap();
     let quota = Quota::per_minute(NonZeroU32::new(MINUTELY_LIMIT).unwrap());
 
-    let pooled_governor = RedisGovernor::new(redis, "rate-limiter-recovery-test");
-    let governor = pooled_governor.instance();
+    let pooled_governor = RedisGovernor::new(redis, "rate-limiter-recovery-test")
+        .expect("failed to create governor");
+    let governor = pooled_governor
+        .instance()
+        .expect("failed to reserve connection");
     governor.wipe();
 
     let redis_limiter = governor.rate_limiter(quota);
@@ -97,8 +103,10 @@ fn rate_limiter_works_when_contended() {
     // Use fixed quota so spillover is not possible which would cause test flakes
     let quota = fixed_quota(LIMIT);
     let redis = redis::Client::open("redis://127.0.0.1/").unwrap();
-    let pool = RedisGovernor::new(redis, PREFIX);
-    let governor = pool.instance();
+    let pool = RedisGovernor::new(redis, PREFIX).expect("failed to create governor");
+    let governor = pool
+        .instance()
+        .expect("failed to reserve connection");
     governor.wipe();
 
     (0..THREADS)
@@ -110,7 +118,9 @@ fn rate_limiter_works_when_contended() {
                 .spawn(move || {
                     // Each thread gets its own limiter conn for better testing
                     // and because the thread is not Send
-                    let governor = pool.instance();
+                    let governor = pool
+                        .instance()
+                        .expect("failed to reserve connection");
                     let redis_limiter = governor.rate_limiter(quota);
 
                     for _ in 0..TRIES_PER_THREAD {
@@ -137,8 +147,10 @@ fn can_maintain_disjoint_rate_limits() {
     // Use fixed quota so spillover is not possible which would cause test flakes
     let quota = fixed_quota(LIMIT);
     let redis = redis::Client::open("redis://127.0.0.1/").unwrap();
-    let pool = RedisGovernor::new(redis, PREFIX);
-    let governor = pool.instance();
+    let pool = RedisGovernor::new(redis, PREFIX).expect("failed to create governor");
+    let governor = pool
+        .instance()
+        .expect("failed to reserve connection");
     governor.wipe();
 
     let mut results = vec![];
@@ -153,7 +165,9 @@ fn can_maintain_disjoint_rate_limits() {
             std::thread::Builder::new()
                 .name(format!("disjoint-rate-limit-job-{}-thread-{}", job, id))
                 .spawn(move || {
-                    let governor = pool.instance();
+                    let governor = pool
+                        .instance()
+                        .expect("failed to reserve connection");
                     let redis_limiter = governor.rate_limiter(quota);
 
                     for _ in 0..TRIES_PER_THREAD {
@@ -175,3 +189,72 @@ fn can_maintain_disjoint_rate_limits() {
         should_rate_limit(&governor.rate_limiter(quota), &(key_name.as_str()));
     }
 }
+
+#[test]
+fn check_keys_rate_limits_each_key_independently() {
+    const LIMIT: u32 = 5u32;
+
+    let redis = redis::Client::open("redis://127.0.0.1/").unwrap();
+    let quota = fixed_quota(LIMIT);
+
+    let pooled_governor = RedisGovernor::new(redis, "batch-check-keys-test")
+        .expect("failed to create governor");
+    let governor = pooled_governor
+        .instance()
+        .expect("failed to reserve connection");
+    governor.wipe();
+
+    let keys = ["a", "b", "c"];
+
+    for _ in 0..LIMIT {
+        let results = governor
+            .check_keys(&keys, quota)
+            .expect("Redis round trip failed");
+        assert!(
+            results.iter().all(Result::is_ok),
+            "all keys should be under their own limit: {:?}",
+            results.iter().map(Result::is_ok).collect::<Vec<_>>()
+        );
+    }
+
+    // Every key has now used its whole burst; one more round should
+    // reject all of them.
+    let results = governor
+        .check_keys(&keys, quota)
+        .expect("Redis round trip failed");
+    assert!(
+        results.iter().all(Result::is_err),
+        "all keys should be rate limited once their burst is exhausted"
+    );
+}
+
+#[test]
+fn check_n_consumes_multiple_cells_per_key() {
+    const LIMIT: u32 = 10u32;
+
+    let redis = redis::Client::open("redis://127.0.0.1/").unwrap();
+    let quota = fixed_quota(LIMIT);
+
+    let pooled_governor = RedisGovernor::new(redis, "batch-check-n-test")
+        .expect("failed to create governor");
+    let governor = pooled_governor
+        .instance()
+        .expect("failed to reserve connection");
+    governor.wipe();
+
+    let keys = ["a", "b"];
+
+    // Each key consumes 5 cells per call, so a second call should still
+    // fit (5 + 5 == 10), but a third should be rejected.
+    for _ in 0..2 {
+        let results = governor
+            .check_n(&keys, quota, 5)
+            .expect("Redis round trip failed");
+        assert!(results.iter().all(Result::is_ok));
+    }
+
+    let results = governor
+        .check_n(&keys, quota, 5)
+        .expect("Redis round trip failed");
+    assert!(results.iter().all(Result::is_err));
+}